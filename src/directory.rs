@@ -1,15 +1,66 @@
 use tini::Ini;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 #[cfg_attr(any(test, feature = "debug"), derive(Debug))]
+#[cfg_attr(
+    feature = "persist-cache",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub(crate) struct Directory {
     size: u16,
     scale: u16,
     kind: DirectoryKind,
+    context: Option<DirectoryContext>,
+}
+
+/// The `Context` a directory's icons are meant to be used in, as defined by
+/// the [icon theme spec](https://specifications.freedesktop.org/icon-theme-spec/icon-theme-spec-latest.html).
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(any(test, feature = "debug"), derive(Debug))]
+#[cfg_attr(
+    feature = "persist-cache",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum DirectoryContext {
+    Actions,
+    Animations,
+    Applications,
+    Categories,
+    Devices,
+    Emblems,
+    Emotes,
+    International,
+    MimeTypes,
+    Places,
+    Status,
+    Other(String),
+}
+
+impl DirectoryContext {
+    fn parse(context: &str) -> Self {
+        match context {
+            "Actions" => Self::Actions,
+            "Animations" => Self::Animations,
+            "Applications" => Self::Applications,
+            "Categories" => Self::Categories,
+            "Devices" => Self::Devices,
+            "Emblems" => Self::Emblems,
+            "Emotes" => Self::Emotes,
+            "International" => Self::International,
+            "MimeTypes" => Self::MimeTypes,
+            "Places" => Self::Places,
+            "Status" => Self::Status,
+            other => Self::Other(other.to_string()),
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
 #[cfg_attr(any(test, feature = "debug"), derive(Debug))]
+#[cfg_attr(
+    feature = "persist-cache",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum DirectoryKind {
     Fixed,
     Scalable { min_size: u16, max_size: u16 },
@@ -21,6 +72,7 @@ impl Directory {
         let size = ini.get(folder, "Size")?;
         let scale = ini.get(folder, "Scale").unwrap_or(1);
         let kind = ini.get::<String>(folder, "Type")?;
+        let context = ini.get::<String>(folder, "Context").map(|c| DirectoryContext::parse(&c));
         Some(Directory {
             size,
             scale,
@@ -35,9 +87,14 @@ impl Directory {
                 },
                 _ => return None,
             },
+            context,
         })
     }
 
+    pub(crate) fn context(&self) -> Option<&DirectoryContext> {
+        self.context.as_ref()
+    }
+
     pub(crate) fn is_matches(&self, icon_size: u16, icon_scale: u16) -> bool {
         if self.scale() != icon_scale {
             return false;
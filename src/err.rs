@@ -17,6 +17,18 @@ pub enum Error {
     TraverseDir { source: IoError },
     #[error("inheritance cycle detected")]
     CycleDetected,
+    #[cfg(any(feature = "persist-cache", feature = "rasterize"))]
+    #[error("icon cache directory could not be determined")]
+    CacheDirMissing,
+    #[cfg(any(feature = "persist-cache", feature = "rasterize"))]
+    #[error("I/O error at {path:?} while reading or writing the icon cache")]
+    CacheIo { path: PathBuf, source: IoError },
+    #[cfg(feature = "persist-cache")]
+    #[error("failed to (de)serialize icon cache snapshot")]
+    InvalidSnapshot { source: bincode::Error },
+    #[cfg(feature = "rasterize")]
+    #[error("failed to rasterize icon at {path:?}")]
+    RasterizeFailed { path: PathBuf },
 }
 
 pub type Result<T, E = Error> = core::result::Result<T, E>;
@@ -6,7 +6,12 @@
 //! # Feature list
 //!
 //! - Multiple themes support, including inherited;
-//! - Advanced lookup among all found
+//! - Advanced lookup among all found;
+//! - Detection of the user's configured system theme;
+//! - Iterating over every matching icon via [Cache::lookup_all];
+//! - Optional on-disk caching of the scanned index (`persist-cache` feature);
+//! - Restricting lookups to a given directory `Context` (e.g. `MimeTypes`);
+//! - Rasterizing scalable icons to a cached PNG (`rasterize` feature)
 //!
 //! # Example
 //!
@@ -28,15 +33,27 @@
 //! [freedesktop-icons](https://crates.io/crates/freedesktop-icons) might be a better option if you only need a few icons to search.
 //! ```
 
+pub use directory::DirectoryContext;
 pub use err::{Error, Result};
 pub use lookup::{Cache, LookupParam};
 pub use theme::IconInfo;
 
 pub(crate) use directory::Directory;
-pub(crate) use lookup::find_dir_icons;
+pub(crate) use lookup::{find_dir_icons, PIXMAPS_DIR};
 pub(crate) use theme::{IconSearch, Theme};
 
 mod directory;
 mod err;
 mod lookup;
+#[cfg(feature = "persist-cache")]
+mod persist;
+#[cfg(feature = "rasterize")]
+mod rasterize;
 mod theme;
+
+/// Guards the process-global env vars (`HOME`, `XDG_CONFIG_HOME`,
+/// `XDG_DATA_DIRS`, `XDG_CACHE_HOME`, ...) that several modules' tests read
+/// or write, since `cargo test` runs tests in the same process concurrently
+/// and two tests racing on the same var would make each other flaky.
+#[cfg(test)]
+pub(crate) static ENV_MUTEX: std::sync::Mutex<()> = std::sync::Mutex::new(());
@@ -1,17 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use either::Either;
+use tini::Ini;
 
-use crate::{Error, IconInfo, IconSearch, Result, Theme};
+use crate::{DirectoryContext, Error, IconInfo, IconSearch, Result, Theme};
 
 const DEFAULT_THEME: &str = "hicolor";
 
+pub(crate) const PIXMAPS_DIR: &str = "/usr/share/pixmaps";
+
+/// `(file relative to the config dir, section, key)` sources probed by
+/// [Cache::detect_system_theme], in priority order.
+const THEME_SOURCES: &[(&str, &str, &str)] = &[
+    ("kdeglobals", "Icons", "Theme"),
+    ("gtk-4.0/settings.ini", "Settings", "gtk-icon-theme-name"),
+    ("gtk-3.0/settings.ini", "Settings", "gtk-icon-theme-name"),
+];
+
 /// Icon cache, before lookups one may need to load required theme(s)
 /// explicitly either with [Cache::load] or [Cache::load_default].
 pub struct Cache {
     themes: HashMap<String, Vec<Theme>>,
     pixmaps: HashMap<String, PathBuf>,
+    #[cfg(feature = "persist-cache")]
+    snapshot: crate::persist::Snapshot,
 }
 
 /// Search parameters for [Cache::lookup_param] search.
@@ -20,21 +33,38 @@ pub struct LookupParam<'a> {
     theme: Option<&'a str>,
     size: Option<u16>,
     scale: Option<u16>,
+    fallback: bool,
+    context: Option<DirectoryContext>,
+}
+
+/// The subset of [LookupParam] relevant to [Cache::collect_themed]'s recursion,
+/// kept together so it doesn't balloon the argument list on every call.
+struct CollectParams<'a> {
+    name: &'a str,
+    context: Option<&'a DirectoryContext>,
+    fallback: bool,
 }
 
 impl Cache {
     /// Creates new cache. Most of the lookups are to be failed at this point.
     /// Consider loading icons afterwards.
     pub fn new() -> Result<Self> {
+        #[cfg(feature = "persist-cache")]
+        let snapshot = crate::persist::Snapshot::load();
+
+        #[cfg(feature = "persist-cache")]
+        let pixmaps = match snapshot.pixmaps() {
+            Some(pixmaps) => pixmaps.clone(),
+            None => scan_pixmaps()?,
+        };
+        #[cfg(not(feature = "persist-cache"))]
+        let pixmaps = scan_pixmaps()?;
+
         Ok(Self {
             themes: HashMap::new(),
-            pixmaps: {
-                let mut pixmaps = HashMap::new();
-                crate::find_dir_icons("/usr/share/pixmaps", |icon_name, path| {
-                    pixmaps.insert(icon_name.into(), path);
-                })?;
-                pixmaps
-            },
+            pixmaps,
+            #[cfg(feature = "persist-cache")]
+            snapshot,
         })
     }
 
@@ -48,9 +78,32 @@ impl Cache {
         self.load(DEFAULT_THEME)
     }
 
+    /// Detects the icon theme the user has configured, probing KDE's
+    /// `kdeglobals` and GTK's `settings.ini` under `$XDG_CONFIG_HOME`
+    /// (falling back to `~/.config`), in that order. Falls back to
+    /// [DEFAULT_THEME] if none of the known sources specify a theme.
+    pub fn detect_system_theme() -> String {
+        config_dir()
+            .and_then(|dir| detect_theme_in(&dir))
+            .unwrap_or_else(|| DEFAULT_THEME.to_string())
+    }
+
+    /// Loads the icon theme detected by [Cache::detect_system_theme].
+    pub fn load_system_theme(&mut self) -> Result<()> {
+        self.load(Self::detect_system_theme())
+    }
+
     /// Load icons for specified icon theme.
     pub fn load(&mut self, theme: impl Into<String>) -> Result<()> {
-        self.load_inner(theme, 0)
+        self.load_inner(theme, 0)?;
+
+        #[cfg(feature = "persist-cache")]
+        {
+            self.snapshot.set_pixmaps(self.pixmaps.clone());
+            self.snapshot.save()?;
+        }
+
+        Ok(())
     }
 
     fn load_inner(&mut self, theme: impl Into<String>, depth: usize) -> Result<()> {
@@ -67,12 +120,29 @@ impl Cache {
         for path in search_dirs() {
             let path = path.join(&theme);
             if path.exists() {
-                let t = Theme::new(path)?;
+                #[cfg(feature = "persist-cache")]
+                if self.snapshot.is_fresh(&theme, &path) {
+                    if let Some(cached) = self.snapshot.themes(&theme, &path).map(<[Theme]>::to_vec) {
+                        for t in &cached {
+                            for inherits in t.inherits() {
+                                self.load_inner(inherits.clone(), depth + 1)?;
+                            }
+                        }
+                        self.themes.entry(theme.clone()).or_default().extend(cached);
+                        continue;
+                    }
+                }
+
+                let t = Theme::new(path.clone())?;
 
-                if let Some(inherits) = t.inherits() {
-                    self.load_inner(inherits, depth + 1)?;
+                for inherits in t.inherits() {
+                    self.load_inner(inherits.clone(), depth + 1)?;
                 }
 
+                #[cfg(feature = "persist-cache")]
+                self.snapshot
+                    .insert_theme(theme.clone(), &path, vec![t.clone()]);
+
                 self.themes.entry(theme.clone()).or_default().push(t);
             }
         }
@@ -93,7 +163,7 @@ impl Cache {
     where
         F: FnMut(&[IconInfo]) -> Option<usize> + Copy,
     {
-        self.lookup_themed(theme.into().unwrap_or(DEFAULT_THEME), name, f, 0)
+        self.lookup_themed(theme.into().unwrap_or(DEFAULT_THEME), name, f, true, 0)
             .map(|s| s.path())
             .or_else(|| self.pixmaps.get(name).cloned())
     }
@@ -112,10 +182,16 @@ impl Cache {
             param.name,
             |infos| {
                 let (icon_size, icon_scale) = (param.size(), param.scale());
+                let matches_context = |i: &IconInfo| {
+                    param
+                        .context
+                        .as_ref()
+                        .is_none_or(|c| i.context() == Some(c))
+                };
 
                 if let Some(idx) = infos
                     .iter()
-                    .position(|i| i.directory().is_matches(icon_size, icon_scale))
+                    .position(|i| matches_context(i) && i.directory().is_matches(icon_size, icon_scale))
                 {
                     return Some(idx);
                 }
@@ -123,6 +199,7 @@ impl Cache {
                 if let Some((idx, _)) = infos
                     .iter()
                     .enumerate()
+                    .filter(|(_, i)| matches_context(i))
                     .min_by_key(|(_, i)| i.directory().size_distance(icon_size, icon_scale))
                 {
                     return Some(idx);
@@ -130,10 +207,114 @@ impl Cache {
 
                 None
             },
+            param.fallback,
             0,
         )
         .map(|s| s.path())
-        .or_else(|| self.pixmaps.get(param.name).cloned())
+        .or_else(|| {
+            // `/usr/share/pixmaps` icons carry no `Context`, so a restricted
+            // lookup can't tell whether one matches; skip the fallback
+            // rather than returning a context-less icon regardless.
+            if param.fallback && param.context.is_none() {
+                self.pixmaps.get(param.name).cloned()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Icon lookup with a provided [LookupParam] that yields every matching
+    /// [IconInfo] across the requested theme and its inherited themes,
+    /// ordered by ascending distance from the requested size/scale and then
+    /// by inheritance depth (closer themes first).
+    pub fn lookup_all(&self, param: LookupParam<'_>) -> impl Iterator<Item = IconInfo> {
+        let (size, scale) = (param.size(), param.scale());
+
+        let mut found = Vec::new();
+        let mut visited = HashSet::new();
+        let collect = CollectParams {
+            name: param.name,
+            context: param.context.as_ref(),
+            fallback: param.fallback,
+        };
+        self.collect_themed(
+            param.theme.unwrap_or(DEFAULT_THEME),
+            &collect,
+            0,
+            &mut visited,
+            &mut found,
+        );
+
+        found.sort_by_key(|(depth, info)| (info.directory().size_distance(size, scale), *depth));
+        found.into_iter().map(|(_, info)| info)
+    }
+
+    /// Resolves `param` to a displayable PNG path: the closest-matching PNG
+    /// among [Cache::lookup_all]'s candidates at the best size distance, or
+    /// (if none of those are a PNG) that distance's closest SVG rasterized
+    /// (and cached) on the fly.
+    #[cfg(feature = "rasterize")]
+    pub fn lookup_rasterized(&self, param: LookupParam<'_>) -> Option<PathBuf> {
+        let (size, scale) = (param.size(), param.scale());
+
+        let mut candidates = self.lookup_all(param).peekable();
+        let best_distance = candidates.peek()?.directory().size_distance(size, scale);
+
+        let mut closest_svg = None;
+        for icon in candidates.take_while(|icon| icon.directory().size_distance(size, scale) == best_distance) {
+            if icon.is_png() {
+                return Some(icon.path().to_path_buf());
+            }
+            if closest_svg.is_none() && icon.is_svg() {
+                closest_svg = Some(icon);
+            }
+        }
+
+        let svg = closest_svg?;
+        crate::rasterize::rasterized(&svg, size, scale).ok()
+    }
+
+    fn collect_themed(
+        &self,
+        theme: &str,
+        params: &CollectParams<'_>,
+        depth: usize,
+        visited: &mut HashSet<String>,
+        out: &mut Vec<(usize, IconInfo)>,
+    ) {
+        // In case of cyclic inherits
+        if depth > 10 {
+            return;
+        }
+
+        // Diamond inheritance (e.g. two themes sharing an ancestor) would
+        // otherwise walk the shared ancestor once per path and duplicate its icons.
+        if !visited.insert(theme.to_string()) {
+            return;
+        }
+
+        let themes = match self.themes.get(theme) {
+            Some(themes) => themes,
+            None => return,
+        };
+
+        for theme in themes {
+            out.extend(
+                theme
+                    .icon_infos(params.name)
+                    .filter(|info| params.context.is_none_or(|c| info.context() == Some(c)))
+                    .cloned()
+                    .map(|info| (depth, info)),
+            );
+        }
+
+        if !params.fallback {
+            return;
+        }
+
+        for inherited in themes.iter().flat_map(|t| t.inherits()) {
+            self.collect_themed(inherited, params, depth + 1, visited, out);
+        }
     }
 
     fn lookup_themed<'a, F>(
@@ -141,6 +322,7 @@ impl Cache {
         theme: &str,
         icon_name: &'a str,
         f: F,
+        fallback: bool,
         depth: usize,
     ) -> Option<IconSearch<'a>>
     where
@@ -158,8 +340,13 @@ impl Cache {
             }
         }
 
-        for theme in themes.iter().filter_map(|t| t.inherits()) {
-            if let Some(search) = self.lookup_themed(theme, icon_name, f, depth + 1) {
+        if !fallback {
+            return None;
+        }
+
+        for inherited in themes.iter().flat_map(|t| t.inherits()) {
+            if let Some(search) = self.lookup_themed(inherited, icon_name, f, fallback, depth + 1)
+            {
                 return Some(search);
             }
         }
@@ -175,6 +362,8 @@ impl<'a> LookupParam<'a> {
             theme: None,
             size: None,
             scale: None,
+            fallback: true,
+            context: None,
         }
     }
 
@@ -183,6 +372,21 @@ impl<'a> LookupParam<'a> {
         self
     }
 
+    /// Whether to fall back to inherited themes and `/usr/share/pixmaps`
+    /// when the requested theme doesn't contain the icon. Defaults to `true`;
+    /// pass `false` to only search the themes named by [LookupParam::with_theme].
+    pub fn with_fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Restricts the lookup to icons declared under the given [DirectoryContext],
+    /// e.g. only `MimeTypes` icons.
+    pub fn with_context(mut self, context: DirectoryContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
     pub fn with_size(mut self, size: u16) -> Self {
         self.size = Some(size);
         self
@@ -198,10 +402,32 @@ impl<'a> LookupParam<'a> {
     }
 
     fn scale(&self) -> u16 {
-        self.size.unwrap_or(1)
+        self.scale.unwrap_or(1)
     }
 }
 
+fn scan_pixmaps() -> Result<HashMap<String, PathBuf>> {
+    let mut pixmaps = HashMap::new();
+    crate::find_dir_icons(PIXMAPS_DIR, |icon_name, path| {
+        pixmaps.insert(icon_name.into(), path);
+    })?;
+    Ok(pixmaps)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok()
+}
+
+fn detect_theme_in(config_dir: &Path) -> Option<String> {
+    THEME_SOURCES.iter().find_map(|(file, section, key)| {
+        let ini = Ini::from_file(&config_dir.join(file)).ok()?;
+        ini.get::<String>(section, key)
+    })
+}
+
 fn search_dirs() -> impl Iterator<Item = PathBuf> {
     use std::iter::once;
 
@@ -265,8 +491,16 @@ where
 mod tests {
     use super::*;
 
+    /// Acquires [crate::ENV_MUTEX], recovering from a poisoned lock left by a
+    /// prior test panic so one broken test doesn't cascade into spurious
+    /// failures in every other env-mutating test.
+    fn lock_env() -> std::sync::MutexGuard<'static, ()> {
+        crate::ENV_MUTEX.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     #[test]
     fn default_search_dirs() {
+        let _guard = lock_env();
         std::env::remove_var("XDG_DATA_DIRS");
         std::env::set_var("HOME", "/tmp");
         // `/usr/share/pixmaps` handled separately as it doesn't have themes.
@@ -277,4 +511,222 @@ mod tests {
                 .collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn detect_theme_precedence_and_fallback() {
+        let _guard = lock_env();
+
+        let config_dir = std::env::temp_dir().join(format!(
+            "freedesktop-icon-lookup-test-theme-detect-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::env::set_var("XDG_CONFIG_HOME", &config_dir);
+
+        // None of the known sources are present: falls back to the default theme.
+        assert_eq!(Cache::detect_system_theme(), DEFAULT_THEME);
+
+        // gtk-3.0 alone is honored.
+        std::fs::create_dir_all(config_dir.join("gtk-3.0")).unwrap();
+        std::fs::write(
+            config_dir.join("gtk-3.0/settings.ini"),
+            "[Settings]\ngtk-icon-theme-name=Gtk3Theme\n",
+        )
+        .unwrap();
+        assert_eq!(Cache::detect_system_theme(), "Gtk3Theme");
+
+        // gtk-4.0 takes priority over gtk-3.0.
+        std::fs::create_dir_all(config_dir.join("gtk-4.0")).unwrap();
+        std::fs::write(
+            config_dir.join("gtk-4.0/settings.ini"),
+            "[Settings]\ngtk-icon-theme-name=Gtk4Theme\n",
+        )
+        .unwrap();
+        assert_eq!(Cache::detect_system_theme(), "Gtk4Theme");
+
+        // kdeglobals takes priority over both GTK sources.
+        std::fs::write(config_dir.join("kdeglobals"), "[Icons]\nTheme=KdeTheme\n").unwrap();
+        assert_eq!(Cache::detect_system_theme(), "KdeTheme");
+
+        std::fs::remove_dir_all(&config_dir).ok();
+    }
+
+    /// Writes a minimal theme under `dirs_root/icons/<name>` with a single
+    /// `48x48/apps`-shaped directory (at `size`), declaring `inherits` and
+    /// optionally dropping an empty `<icon>.<ext>` icon into it.
+    fn write_theme(
+        dirs_root: &Path,
+        name: &str,
+        inherits: &[&str],
+        size: u16,
+        icon: Option<(&str, &str)>,
+    ) {
+        let theme_dir = dirs_root.join("icons").join(name);
+        let leaf = format!("{size}x{size}/apps");
+        std::fs::create_dir_all(theme_dir.join(&leaf)).unwrap();
+
+        let inherits_line = if inherits.is_empty() {
+            String::new()
+        } else {
+            format!("Inherits={}\n", inherits.join(","))
+        };
+        std::fs::write(
+            theme_dir.join("index.theme"),
+            format!(
+                "[Icon Theme]\nName={name}\n{inherits_line}Directories={leaf}\n\n[{leaf}]\nSize={size}\nType=Fixed\nContext=Applications\n"
+            ),
+        )
+        .unwrap();
+
+        if let Some((icon_name, ext)) = icon {
+            std::fs::write(theme_dir.join(&leaf).join(format!("{icon_name}.{ext}")), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn lookup_all_dedupes_diamond_inheritance_and_orders_by_distance() {
+        let _guard = lock_env();
+        let root = std::env::temp_dir().join(format!(
+            "freedesktop-icon-lookup-test-lookup-all-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+
+        // top -> {mid1, mid2} -> base, a diamond with `shared` defined in
+        // both `base` (48x48, reachable via either branch) and `mid1`
+        // (96x96), so the result must contain `base`'s icon exactly once,
+        // ordered ahead of `mid1`'s farther-from-48 one.
+        write_theme(&root, "base", &[], 48, Some(("shared", "png")));
+        write_theme(&root, "mid1", &["base"], 96, Some(("shared", "png")));
+        write_theme(&root, "mid2", &["base"], 48, None);
+        write_theme(&root, "top", &["mid1", "mid2"], 48, None);
+
+        std::env::set_var("HOME", root.join("empty-home"));
+        std::env::set_var("XDG_DATA_DIRS", &root);
+
+        let mut cache = Cache::new().unwrap();
+        cache.load("top").unwrap();
+
+        let icons: Vec<_> = cache
+            .lookup_all(LookupParam::new("shared").with_theme(Some("top")))
+            .collect();
+
+        let sizes: Vec<_> = icons.iter().map(IconInfo::size).collect();
+        assert_eq!(sizes, vec![48, 96]);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn with_fallback_false_does_not_search_inherited_themes() {
+        let _guard = lock_env();
+        let root = std::env::temp_dir().join(format!(
+            "freedesktop-icon-lookup-test-fallback-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+
+        // `solo` only exists in `base`; `child` merely inherits it.
+        write_theme(&root, "base", &[], 48, Some(("solo", "png")));
+        write_theme(&root, "child", &["base"], 48, None);
+
+        std::env::set_var("HOME", root.join("empty-home"));
+        std::env::set_var("XDG_DATA_DIRS", &root);
+
+        let mut cache = Cache::new().unwrap();
+        cache.load("child").unwrap();
+
+        assert!(cache
+            .lookup_param(LookupParam::new("solo").with_theme(Some("child")))
+            .is_some());
+        assert!(cache
+            .lookup_param(LookupParam::new("solo").with_theme(Some("child")).with_fallback(false))
+            .is_none());
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn with_context_rejects_a_same_named_icon_from_another_context() {
+        let _guard = lock_env();
+        let root = std::env::temp_dir().join(format!(
+            "freedesktop-icon-lookup-test-context-{}",
+            std::process::id()
+        ));
+        let theme_dir = root.join("icons").join("ctxtheme");
+        std::fs::create_dir_all(theme_dir.join("48x48/apps")).unwrap();
+        std::fs::create_dir_all(theme_dir.join("48x48/mimetypes")).unwrap();
+        std::fs::write(
+            theme_dir.join("index.theme"),
+            "[Icon Theme]\n\
+             Name=CtxTheme\n\
+             Directories=48x48/apps,48x48/mimetypes\n\
+             \n\
+             [48x48/apps]\n\
+             Size=48\n\
+             Type=Fixed\n\
+             Context=Applications\n\
+             \n\
+             [48x48/mimetypes]\n\
+             Size=48\n\
+             Type=Fixed\n\
+             Context=MimeTypes\n",
+        )
+        .unwrap();
+        std::fs::write(theme_dir.join("48x48/apps/app.png"), "").unwrap();
+        std::fs::write(theme_dir.join("48x48/mimetypes/app.png"), "").unwrap();
+
+        std::env::set_var("HOME", root.join("empty-home"));
+        std::env::set_var("XDG_DATA_DIRS", &root);
+
+        let mut cache = Cache::new().unwrap();
+        cache.load("ctxtheme").unwrap();
+
+        let path = cache
+            .lookup_param(
+                LookupParam::new("app")
+                    .with_theme(Some("ctxtheme"))
+                    .with_context(DirectoryContext::MimeTypes),
+            )
+            .expect("a MimeTypes `app` icon exists");
+        assert!(path.ends_with("48x48/mimetypes/app.png"), "{path:?}");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[cfg(feature = "rasterize")]
+    #[test]
+    fn lookup_rasterized_prefers_an_existing_png_over_rasterizing_an_svg() {
+        let _guard = lock_env();
+        let root = std::env::temp_dir().join(format!(
+            "freedesktop-icon-lookup-test-rasterized-{}",
+            std::process::id()
+        ));
+        let leaf = root.join("icons/rastertheme/48x48/apps");
+        std::fs::create_dir_all(&leaf).unwrap();
+        std::fs::write(
+            root.join("icons/rastertheme/index.theme"),
+            "[Icon Theme]\nName=RasterTheme\nDirectories=48x48/apps\n\n\
+             [48x48/apps]\nSize=48\nType=Fixed\nContext=Applications\n",
+        )
+        .unwrap();
+        std::fs::write(leaf.join("icon.png"), "").unwrap();
+        // Deliberately not valid SVG: if `lookup_rasterized` regressed to
+        // rasterizing instead of preferring the PNG, this would fail to
+        // parse and the lookup would return `None`, so the test fails loudly.
+        std::fs::write(leaf.join("icon.svg"), "not actually an svg").unwrap();
+
+        std::env::set_var("HOME", root.join("empty-home"));
+        std::env::set_var("XDG_DATA_DIRS", &root);
+
+        let mut cache = Cache::new().unwrap();
+        cache.load("rastertheme").unwrap();
+
+        let path = cache
+            .lookup_rasterized(LookupParam::new("icon").with_theme(Some("rastertheme")))
+            .expect("the PNG candidate should be returned directly");
+        assert!(path.ends_with("icon.png"), "{path:?}");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
 }
@@ -0,0 +1,192 @@
+//! On-disk snapshot of a scanned [Cache](crate::Cache), so a new process can
+//! skip re-traversing theme directories when nothing has changed since the
+//! snapshot was written. Enabled with the `persist-cache` feature.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use fs2::FileExt;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use tini::Ini;
+
+use crate::{Error, Result, Theme};
+
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    themes: HashMap<(String, PathBuf), Vec<Theme>>,
+    pixmaps: HashMap<String, PathBuf>,
+    pixmaps_mtime: Option<SystemTime>,
+    mtimes: HashMap<(String, PathBuf), SystemTime>,
+}
+
+impl Snapshot {
+    /// Loads the snapshot written by a previous process, if any. A missing or
+    /// unreadable snapshot is treated as an empty one rather than an error,
+    /// so the cache simply falls back to a full filesystem scan.
+    pub(crate) fn load() -> Self {
+        Self::try_load().unwrap_or_default()
+    }
+
+    fn try_load() -> Option<Self> {
+        let path = snapshot_path()?;
+        let lock = File::open(lock_path(&path)).ok()?;
+        lock.lock_shared().ok()?;
+        let file = File::open(path).ok()?;
+        let mmap = unsafe { Mmap::map(&file) }.ok()?;
+        bincode::deserialize(&mmap).ok()
+    }
+
+    /// Whether the cached entry for `theme` at `path` is still up to date.
+    /// Keyed on both, since the same theme name can exist under more than
+    /// one search directory, each with its own modification time.
+    pub(crate) fn is_fresh(&self, theme: &str, path: &Path) -> bool {
+        let key = (theme.to_string(), path.to_path_buf());
+        self.themes.contains_key(&key)
+            && theme_mtime(path)
+                .map(|mtime| self.mtimes.get(&key) == Some(&mtime))
+                .unwrap_or(false)
+    }
+
+    pub(crate) fn themes(&self, theme: &str, path: &Path) -> Option<&[Theme]> {
+        self.themes
+            .get(&(theme.to_string(), path.to_path_buf()))
+            .map(Vec::as_slice)
+    }
+
+    /// The cached `/usr/share/pixmaps` scan, if it's still up to date.
+    pub(crate) fn pixmaps(&self) -> Option<&HashMap<String, PathBuf>> {
+        let mtime = std::fs::metadata(crate::PIXMAPS_DIR).and_then(|m| m.modified()).ok()?;
+        (self.pixmaps_mtime == Some(mtime)).then_some(&self.pixmaps)
+    }
+
+    pub(crate) fn set_pixmaps(&mut self, pixmaps: HashMap<String, PathBuf>) {
+        self.pixmaps_mtime = std::fs::metadata(crate::PIXMAPS_DIR)
+            .and_then(|m| m.modified())
+            .ok();
+        self.pixmaps = pixmaps;
+    }
+
+    pub(crate) fn insert_theme(&mut self, theme: String, path: &Path, scanned: Vec<Theme>) {
+        let key = (theme, path.to_path_buf());
+        if let Ok(mtime) = theme_mtime(path) {
+            self.mtimes.insert(key.clone(), mtime);
+        }
+        self.themes.insert(key, scanned);
+    }
+
+    /// Writes the snapshot back to disk, guarded by the same lock file
+    /// [Snapshot::try_load] takes a shared lock on, so a reader never mmaps a
+    /// file a writer is truncating, and a temp-file-plus-rename swap so a
+    /// reader that already has the old file mapped never sees a torn write.
+    pub(crate) fn save(&self) -> Result<()> {
+        let path = snapshot_path().ok_or(Error::CacheDirMissing)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|source| Error::CacheIo { path: parent.into(), source })?;
+        }
+
+        let lock_path = lock_path(&path);
+        let lock = File::create(&lock_path).map_err(|source| Error::CacheIo { path: lock_path, source })?;
+        lock.lock_exclusive()
+            .map_err(|source| Error::CacheIo { path: path.clone(), source })?;
+
+        let bytes = bincode::serialize(self).map_err(|source| Error::InvalidSnapshot { source })?;
+        let tmp_path = path.with_extension("bin.tmp");
+        File::create(&tmp_path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(|source| Error::CacheIo { path: tmp_path.clone(), source })?;
+        std::fs::rename(&tmp_path, &path).map_err(|source| Error::CacheIo { path, source })?;
+
+        Ok(())
+    }
+}
+
+fn snapshot_path() -> Option<PathBuf> {
+    std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok()
+        .map(|dir| dir.join("freedesktop-icon-lookup").join("index.bin"))
+}
+
+fn lock_path(snapshot_path: &Path) -> PathBuf {
+    snapshot_path.with_extension("lock")
+}
+
+/// The most recent modification time among `index.theme` and the leaf icon
+/// directories it lists (the exact directories [crate::Theme::new] scans),
+/// used to detect a stale snapshot. Stating only the theme directory's
+/// immediate subdirectories (e.g. `48x48`) would miss an icon added or
+/// removed from a nested leaf like `48x48/apps`, since that only touches
+/// `apps`'s mtime.
+fn theme_mtime(theme_path: &Path) -> std::io::Result<SystemTime> {
+    let mut latest = std::fs::metadata(theme_path.join("index.theme"))?.modified()?;
+
+    for dir in leaf_directories(theme_path) {
+        if let Ok(modified) = std::fs::metadata(theme_path.join(dir)).and_then(|m| m.modified()) {
+            latest = latest.max(modified);
+        }
+    }
+
+    Ok(latest)
+}
+
+/// The `Directories`/`ScaledDirectories` leaf dirs declared in `index.theme`.
+fn leaf_directories(theme_path: &Path) -> Vec<String> {
+    let Ok(mut f) = std::fs::File::open(theme_path.join("index.theme")) else {
+        return Vec::new();
+    };
+    let Ok(ini) = Ini::from_reader(&mut f) else {
+        return Vec::new();
+    };
+
+    ini.get_vec::<String>("Icon Theme", "Directories")
+        .into_iter()
+        .chain(ini.get_vec::<String>("Icon Theme", "ScaledDirectories"))
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A private `$XDG_CACHE_HOME` so the test doesn't read or clobber the
+    /// real user's snapshot. Callers must still hold [crate::ENV_MUTEX]
+    /// while `XDG_CACHE_HOME` is set, since the var itself is process-global.
+    fn isolated_cache_home() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "freedesktop-icon-lookup-test-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let _guard = crate::ENV_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
+        let cache_home = isolated_cache_home();
+        std::env::set_var("XDG_CACHE_HOME", &cache_home);
+
+        let theme_path = PathBuf::from("/usr/share/icons/hicolor");
+        let mut snapshot = Snapshot::default();
+        snapshot.insert_theme("hicolor".into(), &theme_path, Vec::new());
+        snapshot.set_pixmaps(HashMap::from([("firefox".to_string(), PathBuf::from("/usr/share/pixmaps/firefox.png"))]));
+        snapshot.save().unwrap();
+
+        let loaded = Snapshot::load();
+        assert!(loaded.themes("hicolor", &theme_path).is_some());
+        assert_eq!(
+            loaded.pixmaps().and_then(|p| p.get("firefox")),
+            Some(&PathBuf::from("/usr/share/pixmaps/firefox.png"))
+        );
+
+        std::fs::remove_dir_all(&cache_home).ok();
+    }
+}
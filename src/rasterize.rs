@@ -0,0 +1,102 @@
+//! Optional SVG-to-PNG rasterization, with a file cache under
+//! `$XDG_CACHE_HOME` keyed on the source SVG's path and modification time, so
+//! a GUI launcher gets a ready-to-display bitmap without re-rendering on
+//! every lookup. Enabled with the `rasterize` feature.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use resvg::tiny_skia;
+use resvg::usvg::{self, TreeParsing};
+
+use crate::{Error, IconInfo, Result};
+
+/// Renders `icon`'s SVG to a PNG at `size * scale` pixels, reusing a
+/// previously rendered file under `$XDG_CACHE_HOME` when it's at least as
+/// recent as the source SVG. Keyed on `icon`'s own resolved path rather than
+/// the theme a lookup was requested against, so an icon resolved through
+/// theme inheritance is rendered once regardless of which theme name
+/// requests it.
+pub(crate) fn rasterized(icon: &IconInfo, size: u16, scale: u16) -> Result<PathBuf> {
+    let dest = cache_path(icon, size, scale).ok_or(Error::CacheDirMissing)?;
+
+    if is_fresh(icon.path(), &dest) {
+        return Ok(dest);
+    }
+
+    render(icon.path(), &dest, size.saturating_mul(scale))?;
+    Ok(dest)
+}
+
+fn cache_path(icon: &IconInfo, size: u16, scale: u16) -> Option<PathBuf> {
+    let cache_dir = std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok()?;
+
+    Some(
+        cache_dir
+            .join("freedesktop-icon-lookup")
+            .join(size.saturating_mul(scale).to_string())
+            .join(format!("{}.png", source_key(icon.path())?)),
+    )
+}
+
+/// A cache file name unique to `path`'s full location, not just its file
+/// stem: two icons sharing a stem but living under different directories or
+/// contexts (e.g. `places/folder.svg` vs `mimetypes/folder.svg`) must not
+/// collide on the same cache entry.
+fn source_key(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+
+    Some(format!("{stem}-{:016x}", hasher.finish()))
+}
+
+fn is_fresh(source: &Path, cached: &Path) -> bool {
+    match (mtime(source), mtime(cached)) {
+        (Ok(source_mtime), Ok(cached_mtime)) => cached_mtime >= source_mtime,
+        _ => false,
+    }
+}
+
+fn mtime(path: &Path) -> std::io::Result<SystemTime> {
+    std::fs::metadata(path)?.modified()
+}
+
+fn render(source: &Path, dest: &Path, pixels: u16) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|source_err| Error::CacheIo { path: parent.into(), source: source_err })?;
+    }
+
+    let data = std::fs::read(source).map_err(|source_err| Error::CacheIo {
+        path: source.into(),
+        source: source_err,
+    })?;
+    let svg_tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+        .map_err(|_| Error::RasterizeFailed { path: source.into() })?;
+    let tree = resvg::Tree::from_usvg(&svg_tree);
+
+    let pixels = u32::from(pixels.max(1));
+    let mut pixmap =
+        tiny_skia::Pixmap::new(pixels, pixels).ok_or_else(|| Error::RasterizeFailed {
+            path: source.into(),
+        })?;
+
+    let scale = pixels as f32 / tree.size.width().max(tree.size.height());
+    tree.render(
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap
+        .save_png(dest)
+        .map_err(|_| Error::RasterizeFailed { path: source.into() })?;
+
+    Ok(())
+}
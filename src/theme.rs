@@ -4,9 +4,14 @@ use std::path::{Path, PathBuf};
 
 use tini::Ini;
 
-use crate::{Directory, Error, Result};
+use crate::{Directory, DirectoryContext, Error, Result};
 
+#[derive(Clone)]
 #[cfg_attr(any(test, feature = "debug"), derive(Debug))]
+#[cfg_attr(
+    feature = "persist-cache",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub(crate) struct Theme {
     path: PathBuf,
     icon_infos: HashMap<String, Vec<IconInfo>>,
@@ -44,7 +49,10 @@ impl Theme {
                 icon_infos
                     .entry(icon_name.into())
                     .or_default()
-                    .push(IconInfo { path, directory });
+                    .push(IconInfo {
+                        path,
+                        directory: directory.clone(),
+                    });
             })?;
         }
 
@@ -72,6 +80,10 @@ impl Theme {
     pub(crate) fn inherits(&self) -> &[String] {
         &self.inherits
     }
+
+    pub(crate) fn icon_infos<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a IconInfo> {
+        self.icon_infos.get(name).into_iter().flatten()
+    }
 }
 
 pub(crate) struct IconSearch<'a> {
@@ -86,13 +98,23 @@ impl IconSearch<'_> {
 }
 
 /// Information about found icon
+#[derive(Clone)]
 #[cfg_attr(any(test, feature = "debug"), derive(Debug))]
+#[cfg_attr(
+    feature = "persist-cache",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct IconInfo {
     path: PathBuf,
     directory: Directory,
 }
 
 impl IconInfo {
+    /// Full path to the icon file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     pub fn is_svg(&self) -> bool {
         self.path.extension() == Some(OsStr::new("svg"))
     }
@@ -109,8 +131,14 @@ impl IconInfo {
         self.directory.scale()
     }
 
+    /// The context (e.g. `Applications`, `MimeTypes`) this icon is meant to
+    /// be used in, if the theme's `index.theme` specifies one.
+    pub fn context(&self) -> Option<&DirectoryContext> {
+        self.directory.context()
+    }
+
     pub(crate) fn directory(&self) -> Directory {
-        self.directory
+        self.directory.clone()
     }
 }
 